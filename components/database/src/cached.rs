@@ -0,0 +1,549 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures::compat::Future01CompatExt;
+use futures::prelude::{FutureExt, TryFutureExt};
+use lru::LruCache;
+
+use core_context::Context;
+use core_runtime::{DBOp, DBTransaction, DataCategory, Database, DatabaseError, FutDBResult};
+
+/// Which categories are worth caching. `State` and `Block` are read far more
+/// often than written; `TransactionPool` churns too fast for an LRU entry to
+/// ever be reused, so it defaults to disabled.
+#[derive(Clone)]
+pub struct CacheConfig {
+    pub capacity: usize,
+    pub block: bool,
+    pub transaction: bool,
+    pub receipt: bool,
+    pub state: bool,
+    pub transaction_pool: bool,
+    pub transaction_position: bool,
+    pub metadata: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            capacity: 4096,
+            block: true,
+            transaction: false,
+            receipt: false,
+            state: true,
+            transaction_pool: false,
+            transaction_position: false,
+            metadata: false,
+        }
+    }
+}
+
+impl CacheConfig {
+    fn enabled(&self, c: &DataCategory) -> bool {
+        match c {
+            DataCategory::Block => self.block,
+            DataCategory::Transaction => self.transaction,
+            DataCategory::Receipt => self.receipt,
+            DataCategory::State => self.state,
+            DataCategory::TransactionPool => self.transaction_pool,
+            DataCategory::TransactionPosition => self.transaction_position,
+            DataCategory::Metadata => self.metadata,
+        }
+    }
+}
+
+/// Hit/miss counters so operators can tell whether a given capacity and
+/// category selection is actually earning its keep.
+#[derive(Default)]
+pub struct CacheStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheStats {
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn record_hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_hits(&self, n: u64) {
+        self.hits.fetch_add(n, Ordering::Relaxed);
+    }
+
+    fn record_miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_misses(&self, n: u64) {
+        self.misses.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+type CacheKey = (DataCategory, Vec<u8>);
+// `None` is a cached entry too: it means "confirmed absent from `inner`", so
+// repeated lookups for a key that doesn't exist don't keep falling through.
+type CacheValue = Option<Vec<u8>>;
+
+/// A read-through, write-through LRU cache wrapping any `Database`. Because
+/// it implements `Database` itself, it stacks transparently over `MemoryDB`
+/// today and the RocksDB backend later.
+pub struct CachedDB<D> {
+    inner: Arc<D>,
+    cache: Arc<Mutex<LruCache<CacheKey, CacheValue>>>,
+    config: Arc<CacheConfig>,
+    stats: Arc<CacheStats>,
+    // Bumped by every write path right after it commits to `inner`, before
+    // that path takes the cache lock. A miss-populate snapshots this before
+    // reading `inner` and re-checks it *while holding the cache lock* right
+    // before the put, so a write that lands while the read is in flight can
+    // never be clobbered by the (now stale) read's result: whichever of the
+    // two critical sections runs last under the lock determines the final
+    // entry, and the writer's own put always happens after its own bump.
+    epoch: Arc<AtomicU64>,
+}
+
+impl<D: Database> CachedDB<D> {
+    pub fn new(inner: D, config: CacheConfig) -> Self {
+        CachedDB {
+            inner: Arc::new(inner),
+            cache: Arc::new(Mutex::new(LruCache::new(config.capacity.max(1)))),
+            config: Arc::new(config),
+            stats: Arc::new(CacheStats::default()),
+            epoch: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn stats(&self) -> &CacheStats {
+        &self.stats
+    }
+}
+
+/// A panic elsewhere in the process while the cache lock was held must not
+/// turn a healthy `inner` database into a permanently failing one, so a
+/// poisoned cache is recovered rather than propagated as a `Database` error.
+fn lock_cache<T>(cache: &Mutex<T>) -> MutexGuard<T> {
+    cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl<D: Database + 'static> Database for CachedDB<D> {
+    fn get(&self, ctx: Context, c: DataCategory, key: &[u8]) -> FutDBResult<Option<Vec<u8>>> {
+        let inner = Arc::clone(&self.inner);
+        let cache = Arc::clone(&self.cache);
+        let config = Arc::clone(&self.config);
+        let stats = Arc::clone(&self.stats);
+        let epoch = Arc::clone(&self.epoch);
+        let cache_key: CacheKey = (c.clone(), key.to_vec());
+
+        let fut = async move {
+            if config.enabled(&cache_key.0) {
+                if let Some(value) = lock_cache(&cache).get(&cache_key) {
+                    stats.record_hit();
+                    return Ok(value.clone());
+                }
+            }
+            stats.record_miss();
+
+            let epoch_before = epoch.load(Ordering::SeqCst);
+            let value = inner
+                .get(ctx, cache_key.0.clone(), &cache_key.1)
+                .compat()
+                .await?;
+
+            if config.enabled(&cache_key.0) {
+                // Re-check under the same guard as the put: writers bump
+                // `epoch` before taking this lock, so if it still matches
+                // once we hold the lock, no write can land between the
+                // check and the put underneath us.
+                let mut cache = lock_cache(&cache);
+                if epoch.load(Ordering::SeqCst) == epoch_before {
+                    cache.put(cache_key, value.clone());
+                }
+            }
+
+            Ok(value)
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn get_batch(
+        &self,
+        ctx: Context,
+        c: DataCategory,
+        keys: &[Vec<u8>],
+    ) -> FutDBResult<Vec<Option<Vec<u8>>>> {
+        let inner = Arc::clone(&self.inner);
+        let cache = Arc::clone(&self.cache);
+        let config = Arc::clone(&self.config);
+        let stats = Arc::clone(&self.stats);
+        let epoch = Arc::clone(&self.epoch);
+        let keys = keys.to_vec();
+        let cache_enabled = config.enabled(&c);
+
+        let fut = async move {
+            let mut results: Vec<Option<Vec<u8>>> = vec![None; keys.len()];
+            let mut misses = Vec::new();
+
+            if cache_enabled {
+                let mut cache = lock_cache(&cache);
+                for (i, key) in keys.iter().enumerate() {
+                    match cache.get(&(c.clone(), key.clone())) {
+                        Some(value) => results[i] = value.clone(),
+                        None => misses.push(i),
+                    }
+                }
+            } else {
+                misses.extend(0..keys.len());
+            }
+
+            stats.record_hits((keys.len() - misses.len()) as u64);
+            stats.record_misses(misses.len() as u64);
+
+            if !misses.is_empty() {
+                let epoch_before = epoch.load(Ordering::SeqCst);
+                let miss_keys: Vec<Vec<u8>> = misses.iter().map(|&i| keys[i].clone()).collect();
+                let fetched = inner.get_batch(ctx, c.clone(), &miss_keys).compat().await?;
+
+                if cache_enabled {
+                    // See `get`: re-check under the lock that guards the put.
+                    let mut cache = lock_cache(&cache);
+                    if epoch.load(Ordering::SeqCst) == epoch_before {
+                        for (&idx, value) in misses.iter().zip(fetched.iter()) {
+                            cache.put((c.clone(), keys[idx].clone()), value.clone());
+                        }
+                    }
+                }
+
+                for (idx, value) in misses.into_iter().zip(fetched.into_iter()) {
+                    results[idx] = value;
+                }
+            }
+
+            Ok(results)
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn contains(&self, ctx: Context, c: DataCategory, key: &[u8]) -> FutDBResult<bool> {
+        let inner = Arc::clone(&self.inner);
+        let cache = Arc::clone(&self.cache);
+        let config = Arc::clone(&self.config);
+        let stats = Arc::clone(&self.stats);
+        let epoch = Arc::clone(&self.epoch);
+        let cache_key: CacheKey = (c.clone(), key.to_vec());
+
+        let fut = async move {
+            if config.enabled(&cache_key.0) {
+                if let Some(value) = lock_cache(&cache).get(&cache_key) {
+                    stats.record_hit();
+                    return Ok(value.is_some());
+                }
+            }
+            stats.record_miss();
+
+            // Fetch (rather than just `contains`) so the miss can still
+            // populate the cache with the value for a subsequent `get`.
+            let epoch_before = epoch.load(Ordering::SeqCst);
+            let value = inner
+                .get(ctx, cache_key.0.clone(), &cache_key.1)
+                .compat()
+                .await?;
+            let found = value.is_some();
+
+            if config.enabled(&cache_key.0) {
+                // See `get`: re-check under the lock that guards the put.
+                let mut cache = lock_cache(&cache);
+                if epoch.load(Ordering::SeqCst) == epoch_before {
+                    cache.put(cache_key, value);
+                }
+            }
+
+            Ok(found)
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn insert(&self, ctx: Context, c: DataCategory, key: Vec<u8>, value: Vec<u8>) -> FutDBResult<()> {
+        let inner = Arc::clone(&self.inner);
+        let cache = Arc::clone(&self.cache);
+        let config = Arc::clone(&self.config);
+        let epoch = Arc::clone(&self.epoch);
+
+        let fut = async move {
+            inner
+                .insert(ctx, c.clone(), key.clone(), value.clone())
+                .compat()
+                .await?;
+            epoch.fetch_add(1, Ordering::SeqCst);
+
+            if config.enabled(&c) {
+                lock_cache(&cache).put((c, key), Some(value));
+            }
+
+            Ok(())
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn insert_batch(
+        &self,
+        ctx: Context,
+        c: DataCategory,
+        keys: Vec<Vec<u8>>,
+        values: Vec<Vec<u8>>,
+    ) -> FutDBResult<()> {
+        let inner = Arc::clone(&self.inner);
+        let cache = Arc::clone(&self.cache);
+        let config = Arc::clone(&self.config);
+        let epoch = Arc::clone(&self.epoch);
+
+        let fut = async move {
+            if keys.len() != values.len() {
+                return Err(DatabaseError::InvalidData);
+            }
+
+            inner
+                .insert_batch(ctx, c.clone(), keys.clone(), values.clone())
+                .compat()
+                .await?;
+            epoch.fetch_add(1, Ordering::SeqCst);
+
+            if config.enabled(&c) {
+                let mut cache = lock_cache(&cache);
+                for (key, value) in keys.into_iter().zip(values.into_iter()) {
+                    cache.put((c.clone(), key), Some(value));
+                }
+            }
+
+            Ok(())
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn remove(&self, ctx: Context, c: DataCategory, key: &[u8]) -> FutDBResult<()> {
+        let inner = Arc::clone(&self.inner);
+        let cache = Arc::clone(&self.cache);
+        let config = Arc::clone(&self.config);
+        let epoch = Arc::clone(&self.epoch);
+        let key = key.to_vec();
+
+        let fut = async move {
+            inner.remove(ctx, c.clone(), &key).compat().await?;
+            epoch.fetch_add(1, Ordering::SeqCst);
+
+            if config.enabled(&c) {
+                lock_cache(&cache).put((c, key), None);
+            }
+
+            Ok(())
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn remove_batch(&self, ctx: Context, c: DataCategory, keys: &[Vec<u8>]) -> FutDBResult<()> {
+        let inner = Arc::clone(&self.inner);
+        let cache = Arc::clone(&self.cache);
+        let config = Arc::clone(&self.config);
+        let epoch = Arc::clone(&self.epoch);
+        let keys = keys.to_vec();
+
+        let fut = async move {
+            inner.remove_batch(ctx, c.clone(), &keys).compat().await?;
+            epoch.fetch_add(1, Ordering::SeqCst);
+
+            if config.enabled(&c) {
+                let mut cache = lock_cache(&cache);
+                for key in keys {
+                    cache.put((c.clone(), key), None);
+                }
+            }
+
+            Ok(())
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn write(&self, ctx: Context, txn: DBTransaction) -> FutDBResult<()> {
+        let inner = Arc::clone(&self.inner);
+        let cache = Arc::clone(&self.cache);
+        let config = Arc::clone(&self.config);
+        let epoch = Arc::clone(&self.epoch);
+
+        let fut = async move {
+            let ops = txn.into_ops();
+
+            let mut forward = DBTransaction::new();
+            for (c, op) in &ops {
+                match op {
+                    DBOp::Insert { key, value } => {
+                        forward.insert(c.clone(), key.clone(), value.clone())
+                    }
+                    DBOp::Delete { key } => forward.remove(c.clone(), key.clone()),
+                }
+            }
+            inner.write(ctx, forward).compat().await?;
+            epoch.fetch_add(1, Ordering::SeqCst);
+
+            let mut cache = lock_cache(&cache);
+            for (c, op) in ops {
+                if !config.enabled(&c) {
+                    continue;
+                }
+                match op {
+                    DBOp::Insert { key, value } => {
+                        cache.put((c, key), Some(value));
+                    }
+                    DBOp::Delete { key } => {
+                        cache.put((c, key), None);
+                    }
+                }
+            }
+
+            Ok(())
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn iter(&self, ctx: Context, c: DataCategory) -> FutDBResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.inner.iter(ctx, c)
+    }
+
+    fn iter_range(
+        &self,
+        ctx: Context,
+        c: DataCategory,
+        start: Vec<u8>,
+        end: Vec<u8>,
+    ) -> FutDBResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.inner.iter_range(ctx, c, start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures01::future::Future;
+
+    use core_context::Context;
+    use core_runtime::{DataCategory, Database};
+
+    use crate::memory::MemoryDB;
+
+    use super::{CacheConfig, CachedDB};
+
+    #[test]
+    fn test_get_should_populate_cache_on_miss_and_hit_afterwards() {
+        let ctx = Context::new();
+        // Seed the inner DB directly: an insert through `CachedDB` would
+        // write through into the cache and turn the first `get` into a hit.
+        let inner = MemoryDB::new();
+        inner
+            .insert(
+                ctx.clone(),
+                DataCategory::Block,
+                b"test".to_vec(),
+                b"test".to_vec(),
+            )
+            .wait()
+            .unwrap();
+        let db = CachedDB::new(inner, CacheConfig::default());
+
+        assert_eq!(
+            db.get(ctx.clone(), DataCategory::Block, b"test")
+                .wait()
+                .unwrap(),
+            Some(b"test".to_vec())
+        );
+        assert_eq!(
+            db.get(ctx, DataCategory::Block, b"test").wait().unwrap(),
+            Some(b"test".to_vec())
+        );
+
+        assert_eq!(db.stats().hits(), 1);
+        assert_eq!(db.stats().misses(), 1);
+    }
+
+    #[test]
+    fn test_remove_should_invalidate_cache_entry() {
+        let ctx = Context::new();
+        let db = CachedDB::new(MemoryDB::new(), CacheConfig::default());
+
+        db.insert(
+            ctx.clone(),
+            DataCategory::Block,
+            b"test".to_vec(),
+            b"test".to_vec(),
+        )
+        .wait()
+        .unwrap();
+        db.get(ctx.clone(), DataCategory::Block, b"test")
+            .wait()
+            .unwrap();
+
+        db.remove(ctx.clone(), DataCategory::Block, b"test")
+            .wait()
+            .unwrap();
+
+        assert_eq!(
+            db.get(ctx, DataCategory::Block, b"test").wait(),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_get_should_cache_a_negative_lookup() {
+        let ctx = Context::new();
+        let db = CachedDB::new(MemoryDB::new(), CacheConfig::default());
+
+        assert_eq!(
+            db.get(ctx.clone(), DataCategory::Block, b"missing")
+                .wait()
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            db.get(ctx, DataCategory::Block, b"missing").wait().unwrap(),
+            None
+        );
+
+        assert_eq!(db.stats().hits(), 1);
+        assert_eq!(db.stats().misses(), 1);
+    }
+
+    #[test]
+    fn test_disabled_category_should_never_populate_cache() {
+        let ctx = Context::new();
+        let db = CachedDB::new(MemoryDB::new(), CacheConfig::default());
+
+        db.insert(
+            ctx.clone(),
+            DataCategory::TransactionPool,
+            b"test".to_vec(),
+            b"test".to_vec(),
+        )
+        .wait()
+        .unwrap();
+        db.get(ctx.clone(), DataCategory::TransactionPool, b"test")
+            .wait()
+            .unwrap();
+        db.get(ctx, DataCategory::TransactionPool, b"test")
+            .wait()
+            .unwrap();
+
+        assert_eq!(db.stats().hits(), 0);
+        assert_eq!(db.stats().misses(), 2);
+    }
+}
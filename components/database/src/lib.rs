@@ -0,0 +1,4 @@
+pub mod cached;
+pub mod memory;
+pub mod migration;
+pub mod rocksdb;
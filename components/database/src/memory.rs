@@ -4,7 +4,7 @@ use std::sync::{Arc, RwLock};
 use futures::prelude::{FutureExt, TryFutureExt};
 
 use core_context::Context;
-use core_runtime::{DataCategory, Database, DatabaseError, FutDBResult};
+use core_runtime::{DBOp, DBTransaction, DataCategory, Database, DatabaseError, FutDBResult};
 
 pub struct MemoryDB {
     storage: Arc<RwLock<HashMap<Vec<u8>, Vec<u8>>>>,
@@ -142,19 +142,101 @@ impl Database for MemoryDB {
 
         Box::new(fut.boxed().compat())
     }
+
+    fn write(&self, _: Context, txn: DBTransaction) -> FutDBResult<()> {
+        let storage = Arc::clone(&self.storage);
+
+        let fut = async move {
+            // Hold the write lock for the whole batch so no reader can
+            // observe a partially-applied transaction.
+            let mut storage = storage.write().map_err(|_| map_rwlock_err())?;
+            for (c, op) in txn.into_ops() {
+                match op {
+                    DBOp::Insert { key, value } => {
+                        storage.insert(gen_key(&c, key), value);
+                    }
+                    DBOp::Delete { key } => {
+                        storage.remove(&gen_key(&c, key));
+                    }
+                }
+            }
+            Ok(())
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn iter(&self, _: Context, c: DataCategory) -> FutDBResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let storage = Arc::clone(&self.storage);
+
+        let fut = async move {
+            let storage = storage.read().map_err(|_| map_rwlock_err())?;
+            let mut pairs = entries_in_category(&storage, &c);
+            pairs.sort_by(|a, b| a.0.cmp(&b.0));
+            Ok(pairs)
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+
+    fn iter_range(
+        &self,
+        _: Context,
+        c: DataCategory,
+        start: Vec<u8>,
+        end: Vec<u8>,
+    ) -> FutDBResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let storage = Arc::clone(&self.storage);
+
+        let fut = async move {
+            let storage = storage.read().map_err(|_| map_rwlock_err())?;
+            let mut pairs = entries_in_category(&storage, &c)
+                .into_iter()
+                .filter(|(key, _)| key >= &start && key < &end)
+                .collect::<Vec<_>>();
+            pairs.sort_by(|a, b| a.0.cmp(&b.0));
+            Ok(pairs)
+        };
+
+        Box::new(fut.boxed().compat())
+    }
 }
 
-fn gen_key(c: &DataCategory, key: Vec<u8>) -> Vec<u8> {
+fn entries_in_category(
+    storage: &HashMap<Vec<u8>, Vec<u8>>,
+    c: &DataCategory,
+) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let prefix = category_prefix(c);
+    storage
+        .iter()
+        .filter_map(|(key, value)| {
+            key.strip_prefix(prefix)
+                .map(|stripped| (stripped.to_vec(), value.clone()))
+        })
+        .collect()
+}
+
+/// Fixed-length, single-byte tags. Unlike the human-readable names they
+/// replace (`b"transaction-"` is itself a prefix of `b"transaction-pool-"`),
+/// two distinct fixed-length byte strings can never be a prefix of one
+/// another, so `iter`/`iter_range` can safely strip this prefix off without
+/// bleeding into a neighbouring category.
+fn category_prefix(c: &DataCategory) -> &'static [u8] {
     match c {
-        DataCategory::Block => [b"block-".to_vec(), key].concat(),
-        DataCategory::Transaction => [b"transaction-".to_vec(), key].concat(),
-        DataCategory::Receipt => [b"receipt-".to_vec(), key].concat(),
-        DataCategory::State => [b"state-".to_vec(), key].concat(),
-        DataCategory::TransactionPool => [b"transaction-pool-".to_vec(), key].concat(),
-        DataCategory::TransactionPosition => [b"transaction-position-".to_vec(), key].concat(),
+        DataCategory::Block => &[0],
+        DataCategory::Transaction => &[1],
+        DataCategory::Receipt => &[2],
+        DataCategory::State => &[3],
+        DataCategory::TransactionPool => &[4],
+        DataCategory::TransactionPosition => &[5],
+        DataCategory::Metadata => &[6],
     }
 }
 
+fn gen_key(c: &DataCategory, key: Vec<u8>) -> Vec<u8> {
+    [category_prefix(c).to_vec(), key].concat()
+}
+
 fn gen_keys(c: &DataCategory, keys: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
     keys.into_iter().map(|key| gen_key(c, key)).collect()
 }
@@ -318,4 +400,142 @@ mod tests {
         );
         assert_eq!(db.get(ctx, DataCategory::Block, b"test2").wait(), Ok(None));
     }
+
+    #[test]
+    fn test_write_should_apply_ops_across_categories_atomically() {
+        use core_runtime::DBTransaction;
+
+        let ctx = Context::new();
+        let db = MemoryDB::new();
+
+        db.insert(
+            ctx.clone(),
+            DataCategory::Block,
+            b"stale".to_vec(),
+            b"stale".to_vec(),
+        )
+        .wait()
+        .unwrap();
+
+        let mut txn = DBTransaction::new();
+        txn.insert(DataCategory::Block, b"block".to_vec(), b"block".to_vec());
+        txn.insert(
+            DataCategory::Transaction,
+            b"tx".to_vec(),
+            b"tx".to_vec(),
+        );
+        txn.remove(DataCategory::Block, b"stale".to_vec());
+
+        db.write(ctx.clone(), txn).wait().unwrap();
+
+        assert_eq!(
+            db.get(ctx.clone(), DataCategory::Block, b"block")
+                .wait()
+                .unwrap(),
+            Some(b"block".to_vec())
+        );
+        assert_eq!(
+            db.get(ctx.clone(), DataCategory::Transaction, b"tx")
+                .wait()
+                .unwrap(),
+            Some(b"tx".to_vec())
+        );
+        assert_eq!(
+            db.get(ctx, DataCategory::Block, b"stale").wait(),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_iter_should_return_only_keys_in_category_sorted() {
+        let ctx = Context::new();
+        let db = MemoryDB::new();
+
+        db.insert_batch(
+            ctx.clone(),
+            DataCategory::Block,
+            vec![b"b".to_vec(), b"a".to_vec()],
+            vec![b"bval".to_vec(), b"aval".to_vec()],
+        )
+        .wait()
+        .unwrap();
+        db.insert(
+            ctx.clone(),
+            DataCategory::Transaction,
+            b"c".to_vec(),
+            b"cval".to_vec(),
+        )
+        .wait()
+        .unwrap();
+
+        let pairs = db.iter(ctx, DataCategory::Block).wait().unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                (b"a".to_vec(), b"aval".to_vec()),
+                (b"b".to_vec(), b"bval".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_iter_should_not_bleed_into_categories_whose_name_it_prefixes() {
+        let ctx = Context::new();
+        let db = MemoryDB::new();
+
+        db.insert(
+            ctx.clone(),
+            DataCategory::Transaction,
+            b"tx".to_vec(),
+            b"txval".to_vec(),
+        )
+        .wait()
+        .unwrap();
+        db.insert(
+            ctx.clone(),
+            DataCategory::TransactionPool,
+            b"pool".to_vec(),
+            b"poolval".to_vec(),
+        )
+        .wait()
+        .unwrap();
+        db.insert(
+            ctx.clone(),
+            DataCategory::TransactionPosition,
+            b"position".to_vec(),
+            b"positionval".to_vec(),
+        )
+        .wait()
+        .unwrap();
+
+        let pairs = db.iter(ctx, DataCategory::Transaction).wait().unwrap();
+        assert_eq!(pairs, vec![(b"tx".to_vec(), b"txval".to_vec())]);
+    }
+
+    #[test]
+    fn test_iter_range_should_return_half_open_bounded_keys() {
+        let ctx = Context::new();
+        let db = MemoryDB::new();
+
+        db.insert_batch(
+            ctx.clone(),
+            DataCategory::Block,
+            vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()],
+            vec![b"aval".to_vec(), b"bval".to_vec(), b"cval".to_vec()],
+        )
+        .wait()
+        .unwrap();
+
+        let pairs = db
+            .iter_range(ctx, DataCategory::Block, b"a".to_vec(), b"c".to_vec())
+            .wait()
+            .unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                (b"a".to_vec(), b"aval".to_vec()),
+                (b"b".to_vec(), b"bval".to_vec()),
+            ]
+        );
+    }
 }
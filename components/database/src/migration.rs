@@ -0,0 +1,284 @@
+use std::sync::Arc;
+
+use futures::compat::Future01CompatExt;
+use futures::prelude::{FutureExt, TryFutureExt};
+
+use log::info;
+
+use core_context::Context;
+use core_runtime::{DBTransaction, DataCategory, Database, DatabaseError, FutDBResult};
+
+/// Reserved key, stored in the dedicated `Metadata` category (never `State`,
+/// so it can't turn up interleaved with real state entries when a caller
+/// iterates or scans `State`), holding the schema version the database was
+/// last migrated to. A store that has never been migrated is treated as
+/// version `0`.
+const SCHEMA_VERSION_KEY: &[u8] = b"__schema_version__";
+
+/// A single, versioned transformation of the on-disk layout. `apply` reads
+/// whatever it needs from `src` and returns the transformed data as a
+/// `DBTransaction`, so categories can be renamed, split, or re-encoded
+/// without manual data surgery. `Migrator` commits that transaction together
+/// with the schema-version bump in one atomic `Database::write`, so a crash
+/// mid-step can never leave the version bumped without its data, or vice
+/// versa.
+pub trait Migration: Send + Sync {
+    /// The schema version this migration produces once applied.
+    fn version(&self) -> u32;
+
+    fn apply(&self, ctx: Context, src: Arc<dyn Database>) -> FutDBResult<DBTransaction>;
+}
+
+/// Runs every registered `Migration` whose version is newer than the store's
+/// current schema version, in ascending order. Each step's writes and its
+/// schema-version bump are committed together as a single `Database::write`,
+/// so a crash mid-chain resumes from the last completed migration rather
+/// than replaying it or leaving it half-applied.
+pub struct Migrator {
+    migrations: Vec<Box<dyn Migration>>,
+}
+
+impl Migrator {
+    pub fn new() -> Self {
+        Migrator {
+            migrations: Vec::new(),
+        }
+    }
+
+    pub fn register(mut self, migration: Box<dyn Migration>) -> Self {
+        self.migrations.push(migration);
+        self
+    }
+
+    /// Migrates `db` in place, using it as both source and destination of
+    /// every step.
+    pub fn run(self, ctx: Context, db: Arc<dyn Database>) -> FutDBResult<()> {
+        let fut = async move {
+            let current = read_schema_version(ctx.clone(), Arc::clone(&db))
+                .compat()
+                .await?;
+
+            let mut pending: Vec<Box<dyn Migration>> = self
+                .migrations
+                .into_iter()
+                .filter(|m| m.version() > current)
+                .collect();
+            pending.sort_by_key(|m| m.version());
+            let total = pending.len();
+
+            for (i, migration) in pending.into_iter().enumerate() {
+                let version = migration.version();
+                info!(
+                    "running migration {}/{} to schema version {}",
+                    i + 1,
+                    total,
+                    version
+                );
+
+                let mut txn = migration.apply(ctx.clone(), Arc::clone(&db)).compat().await?;
+                txn.insert(
+                    DataCategory::Metadata,
+                    SCHEMA_VERSION_KEY.to_vec(),
+                    version.to_be_bytes().to_vec(),
+                );
+                db.write(ctx.clone(), txn).compat().await?;
+
+                info!(
+                    "migration {}/{} to schema version {} committed",
+                    i + 1,
+                    total,
+                    version
+                );
+            }
+
+            Ok(())
+        };
+
+        Box::new(fut.boxed().compat())
+    }
+}
+
+impl Default for Migrator {
+    fn default() -> Self {
+        Migrator::new()
+    }
+}
+
+fn read_schema_version(ctx: Context, db: Arc<dyn Database>) -> FutDBResult<u32> {
+    let fut = async move {
+        let raw = db
+            .get(ctx, DataCategory::Metadata, SCHEMA_VERSION_KEY)
+            .compat()
+            .await?;
+
+        match raw {
+            Some(bytes) if bytes.len() == 4 => {
+                let mut buf = [0u8; 4];
+                buf.copy_from_slice(&bytes);
+                Ok(u32::from_be_bytes(buf))
+            }
+            Some(_) => Err(DatabaseError::InvalidData),
+            None => Ok(0),
+        }
+    };
+
+    Box::new(fut.boxed().compat())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::compat::Future01CompatExt;
+    use futures::prelude::{FutureExt, TryFutureExt};
+    use futures01::future::Future;
+
+    use core_context::Context;
+    use core_runtime::{DBTransaction, DataCategory, Database, FutDBResult};
+
+    use crate::memory::MemoryDB;
+
+    use super::{Migration, Migrator};
+
+    /// Seeds the pre-migration layout: block headers and bodies interleaved
+    /// in a single `Block` category, keyed as `<hash>-header` / `<hash>-body`.
+    fn seed_old_layout(db: &MemoryDB) {
+        let ctx = Context::new();
+        db.insert(
+            ctx.clone(),
+            DataCategory::Block,
+            b"hash1-header".to_vec(),
+            b"header1".to_vec(),
+        )
+        .wait()
+        .unwrap();
+        db.insert(
+            ctx,
+            DataCategory::Block,
+            b"hash1-body".to_vec(),
+            b"body1".to_vec(),
+        )
+        .wait()
+        .unwrap();
+    }
+
+    /// Splits block headers out of `Block` and into `Transaction` (standing
+    /// in for a dedicated header category in this test) under the bare hash
+    /// as key.
+    struct SplitBlockHeaders;
+
+    impl Migration for SplitBlockHeaders {
+        fn version(&self) -> u32 {
+            1
+        }
+
+        fn apply(&self, ctx: Context, src: Arc<dyn Database>) -> FutDBResult<DBTransaction> {
+            let fut = async move {
+                let header = src
+                    .get(ctx, DataCategory::Block, b"hash1-header")
+                    .compat()
+                    .await?;
+
+                let mut txn = DBTransaction::new();
+                if let Some(header) = header {
+                    txn.insert(DataCategory::Transaction, b"hash1".to_vec(), header);
+                    txn.remove(DataCategory::Block, b"hash1-header".to_vec());
+                }
+                Ok(txn)
+            };
+
+            Box::new(fut.boxed().compat())
+        }
+    }
+
+    #[test]
+    fn test_migrator_applies_pending_migrations_and_data_is_reachable_under_new_scheme() {
+        let ctx = Context::new();
+        let db = Arc::new(MemoryDB::new());
+        seed_old_layout(&db);
+
+        let migrator = Migrator::new().register(Box::new(SplitBlockHeaders));
+        migrator.run(ctx.clone(), Arc::clone(&db) as Arc<dyn Database>).wait().unwrap();
+
+        assert_eq!(
+            db.get(ctx.clone(), DataCategory::Transaction, b"hash1")
+                .wait()
+                .unwrap(),
+            Some(b"header1".to_vec())
+        );
+        assert_eq!(
+            db.get(ctx.clone(), DataCategory::Block, b"hash1-header")
+                .wait(),
+            Ok(None)
+        );
+        assert_eq!(
+            db.get(ctx, DataCategory::Block, b"hash1-body")
+                .wait()
+                .unwrap(),
+            Some(b"body1".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_schema_version_does_not_leak_into_state_iteration() {
+        let ctx = Context::new();
+        let db = Arc::new(MemoryDB::new());
+        seed_old_layout(&db);
+        db.insert(
+            ctx.clone(),
+            DataCategory::State,
+            b"node".to_vec(),
+            b"nodeval".to_vec(),
+        )
+        .wait()
+        .unwrap();
+
+        let migrator = Migrator::new().register(Box::new(SplitBlockHeaders));
+        migrator
+            .run(ctx.clone(), Arc::clone(&db) as Arc<dyn Database>)
+            .wait()
+            .unwrap();
+
+        // The schema version bump lives in `Metadata`, not `State`, so
+        // scanning `State` must see only the real state entry.
+        let state_entries = db.iter(ctx, DataCategory::State).wait().unwrap();
+        assert_eq!(state_entries, vec![(b"node".to_vec(), b"nodeval".to_vec())]);
+    }
+
+    #[test]
+    fn test_migrator_is_idempotent_once_schema_version_is_bumped() {
+        let ctx = Context::new();
+        let db = Arc::new(MemoryDB::new());
+        seed_old_layout(&db);
+
+        let migrator = Migrator::new().register(Box::new(SplitBlockHeaders));
+        migrator
+            .run(ctx.clone(), Arc::clone(&db) as Arc<dyn Database>)
+            .wait()
+            .unwrap();
+
+        // Re-seed the old key: if the migrator re-ran this migration it
+        // would split it again, moving it back into `Transaction`.
+        db.insert(
+            ctx.clone(),
+            DataCategory::Block,
+            b"hash1-header".to_vec(),
+            b"header1".to_vec(),
+        )
+        .wait()
+        .unwrap();
+
+        let migrator = Migrator::new().register(Box::new(SplitBlockHeaders));
+        migrator
+            .run(ctx.clone(), Arc::clone(&db) as Arc<dyn Database>)
+            .wait()
+            .unwrap();
+
+        assert_eq!(
+            db.get(ctx, DataCategory::Block, b"hash1-header")
+                .wait()
+                .unwrap(),
+            Some(b"header1".to_vec())
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt;
+
+use futures01::future::Future;
+
+use core_context::Context;
+
+pub type FutDBResult<T> = Box<dyn Future<Item = T, Error = DatabaseError> + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataCategory {
+    Block,
+    Transaction,
+    Receipt,
+    State,
+    TransactionPool,
+    TransactionPosition,
+    /// Internal bookkeeping (e.g. the schema version) that must never show
+    /// up when a caller iterates or scans one of the data categories above.
+    Metadata,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DatabaseError {
+    NotFound,
+    InvalidData,
+    Internal(String),
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DatabaseError::NotFound => write!(f, "database: not found"),
+            DatabaseError::InvalidData => write!(f, "database: invalid data"),
+            DatabaseError::Internal(msg) => write!(f, "database: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+/// A single put or delete, scoped to a `DataCategory`, as accumulated by a
+/// `DBTransaction`.
+#[derive(Debug, Clone)]
+pub enum DBOp {
+    Insert { key: Vec<u8>, value: Vec<u8> },
+    Delete { key: Vec<u8> },
+}
+
+/// A batch of writes spanning one or more `DataCategory`s, applied under a
+/// single exclusive lock (or a single RocksDB `WriteBatch`) by
+/// `Database::write` so callers never observe it half-applied. Building a
+/// transaction does not touch any internal database lock; only `write`
+/// commits it.
+#[derive(Debug, Clone, Default)]
+pub struct DBTransaction {
+    ops: Vec<(DataCategory, DBOp)>,
+}
+
+impl DBTransaction {
+    pub fn new() -> Self {
+        DBTransaction { ops: Vec::new() }
+    }
+
+    pub fn insert(&mut self, c: DataCategory, key: Vec<u8>, value: Vec<u8>) {
+        self.ops.push((c, DBOp::Insert { key, value }));
+    }
+
+    pub fn remove(&mut self, c: DataCategory, key: Vec<u8>) {
+        self.ops.push((c, DBOp::Delete { key }));
+    }
+
+    pub fn into_ops(self) -> Vec<(DataCategory, DBOp)> {
+        self.ops
+    }
+}
+
+pub trait Database: Send + Sync {
+    fn get(&self, ctx: Context, c: DataCategory, key: &[u8]) -> FutDBResult<Option<Vec<u8>>>;
+
+    fn get_batch(
+        &self,
+        ctx: Context,
+        c: DataCategory,
+        keys: &[Vec<u8>],
+    ) -> FutDBResult<Vec<Option<Vec<u8>>>>;
+
+    fn insert(&self, ctx: Context, c: DataCategory, key: Vec<u8>, value: Vec<u8>) -> FutDBResult<()>;
+
+    fn insert_batch(
+        &self,
+        ctx: Context,
+        c: DataCategory,
+        keys: Vec<Vec<u8>>,
+        values: Vec<Vec<u8>>,
+    ) -> FutDBResult<()>;
+
+    fn contains(&self, ctx: Context, c: DataCategory, key: &[u8]) -> FutDBResult<bool>;
+
+    fn remove(&self, ctx: Context, c: DataCategory, key: &[u8]) -> FutDBResult<()>;
+
+    fn remove_batch(&self, ctx: Context, c: DataCategory, keys: &[Vec<u8>]) -> FutDBResult<()>;
+
+    /// Applies every op in `txn`, across however many categories it touches,
+    /// as a single atomic commit.
+    fn write(&self, ctx: Context, txn: DBTransaction) -> FutDBResult<()>;
+
+    /// Scans every entry in `c`, sorted by key.
+    fn iter(&self, ctx: Context, c: DataCategory) -> FutDBResult<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    /// Scans the half-open range `[start, end)` of `c`, sorted by key.
+    fn iter_range(
+        &self,
+        ctx: Context,
+        c: DataCategory,
+        start: Vec<u8>,
+        end: Vec<u8>,
+    ) -> FutDBResult<Vec<(Vec<u8>, Vec<u8>)>>;
+}